@@ -1,7 +1,13 @@
 //! Error related functionality of wasm3.
+use alloc::boxed::Box;
+use alloc::string::String;
+#[cfg(feature = "backtrace")]
+use alloc::vec::Vec;
+use core::any::Any;
 use core::cmp;
 use core::fmt;
 
+use crate::runtime::Runtime;
 use crate::utils::cstr_to_str;
 
 /// Result alias that uses [`Error`].
@@ -55,8 +61,8 @@ impl Trap {
 }
 
 impl cmp::PartialEq<Wasm3Error> for Trap {
-    fn eq(&self, &Wasm3Error(err): &Wasm3Error) -> bool {
-        self.as_ptr() == err
+    fn eq(&self, other: &Wasm3Error) -> bool {
+        self.as_ptr() == other.0
     }
 }
 
@@ -69,16 +75,27 @@ impl fmt::Display for Trap {
 }
 
 /// Error returned by wasm3.
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub struct Wasm3Error(*const cty::c_char);
+///
+/// Identity is the underlying `M3Result` pointer alone; two `Wasm3Error`s compare equal
+/// whenever they represent the same wasm3 error, regardless of whether either carries
+/// [`ErrorInfo`].
+#[derive(Clone)]
+pub struct Wasm3Error(*const cty::c_char, Option<ErrorInfo>);
 
 impl Wasm3Error {
     /// Check whether this error is the specified trap.
-    pub fn is_trap(self, trap: Trap) -> bool {
+    pub fn is_trap(&self, trap: Trap) -> bool {
         trap.as_ptr() == self.0
     }
 }
 
+impl cmp::PartialEq for Wasm3Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl cmp::Eq for Wasm3Error {}
+
 impl cmp::PartialEq<Trap> for Wasm3Error {
     fn eq(&self, trap: &Trap) -> bool {
         trap.as_ptr() == self.0
@@ -100,7 +117,7 @@ impl fmt::Display for Wasm3Error {
 
 impl From<Trap> for Wasm3Error {
     fn from(trap: Trap) -> Self {
-        Self(trap.as_ptr())
+        Self(trap.as_ptr(), None)
     }
 }
 
@@ -125,7 +142,6 @@ impl From<Wasm3Error> for Trap {
 }
 
 /// Error returned by wasm3-rs.
-#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
     /// An error originating from wasm3 itself may or may not be a trap.
     Wasm3(Wasm3Error),
@@ -139,6 +155,11 @@ pub enum Error {
     ModuleLoadEnvMismatch,
     /// The runtime is active and running, and modules can not be linked to it.
     RuntimeIsActive,
+    /// An error value returned by a host function, recovered via [`Error::downcast_ref`].
+    ///
+    /// Unlike the other variants this one does not originate from wasm3 itself; it carries
+    /// whatever error the linked Rust closure returned.
+    Host(Box<dyn Any + Send + Sync>),
 }
 
 impl Error {
@@ -148,34 +169,120 @@ impl Error {
     pub fn into_trap(self) -> Trap {
         let wasm3_err = match self {
             Error::Wasm3(wasm3) => wasm3,
-            _ => unsafe { Wasm3Error(ffi::m3Err_trapAbort) },
+            _ => Wasm3Error(unsafe { ffi::m3Err_trapAbort }, None),
         };
         wasm3_err.into()
     }
+
+    /// Attempts to downcast the error stored in [`Error::Host`] to a concrete type.
+    ///
+    /// Returns `None` for every other variant, or if the contained value isn't a `T`.
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        match self {
+            Error::Host(err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the process exit code carried by this error, if it is a clean
+    /// [`Trap::Exit`] raised by the guest calling `proc_exit` rather than some other failure.
+    ///
+    /// `runtime` must be the runtime the failing call ran on.
+    pub fn exit_code(&self, runtime: &Runtime) -> Option<i32> {
+        match self {
+            Error::Wasm3(err) if err.is_trap(Trap::Exit) => runtime.exit_code(),
+            _ => None,
+        }
+    }
+
+    /// Returns the structured diagnostic info wasm3 reported for this error, if any.
+    ///
+    /// Only populated for [`Error::Wasm3`], and only when wasm3 had error info available at
+    /// the time the call failed.
+    pub fn info(&self) -> Option<&ErrorInfo> {
+        match self {
+            Error::Wasm3(err) => err.1.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 impl Error {
-    pub(crate) fn from_ffi_res(ptr: ffi::M3Result) -> Result<()> {
+    // A unique sentinel `M3Result` owned by this crate. Host closures that return `Err` hand
+    // the boxed error to the runtime's host error stack and fail the ffi call with this pointer
+    // instead of a real wasm3 error string; `from_ffi_res` recognizes it by address below.
+    pub(crate) fn host_error_sentinel() -> ffi::M3Result {
+        static SENTINEL: cty::c_char = 0;
+        &SENTINEL
+    }
+
+    pub(crate) fn from_ffi_res(ptr: ffi::M3Result, runtime: &Runtime) -> Result<()> {
         if ptr.is_null() {
             Ok(())
+        } else if ptr == Self::host_error_sentinel() {
+            Err(Error::Host(runtime.pop_host_error()))
         } else if unsafe { ptr == ffi::m3Err_functionLookupFailed } {
             Err(Error::FunctionNotFound)
         } else {
-            Err(Error::Wasm3(Wasm3Error(ptr)))
+            #[cfg(feature = "backtrace")]
+            runtime.capture_backtrace();
+            // must run immediately, before any further runtime operation overwrites wasm3's
+            // internal error state
+            let info = runtime.query_error_info();
+            Err(Error::Wasm3(Wasm3Error(ptr, info)))
         }
     }
 
     pub(crate) fn malloc_error() -> Self {
-        Error::Wasm3(Wasm3Error(unsafe { ffi::m3Err_mallocFailed }))
+        Error::Wasm3(Wasm3Error(unsafe { ffi::m3Err_mallocFailed }, None))
     }
 }
 
 #[cfg(feature = "std")]
 impl std::error::Error for Error {}
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Wasm3(err) => f.debug_tuple("Wasm3").field(err).finish(),
+            Error::InvalidFunctionSignature => write!(f, "InvalidFunctionSignature"),
+            Error::FunctionNotFound => write!(f, "FunctionNotFound"),
+            Error::ModuleNotFound => write!(f, "ModuleNotFound"),
+            Error::ModuleLoadEnvMismatch => write!(f, "ModuleLoadEnvMismatch"),
+            Error::RuntimeIsActive => write!(f, "RuntimeIsActive"),
+            Error::Host(_) => write!(f, "Host(..)"),
+        }
+    }
+}
+impl cmp::PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Error::Wasm3(a), Error::Wasm3(b)) => a == b,
+            (Error::InvalidFunctionSignature, Error::InvalidFunctionSignature) => true,
+            (Error::FunctionNotFound, Error::FunctionNotFound) => true,
+            (Error::ModuleNotFound, Error::ModuleNotFound) => true,
+            (Error::ModuleLoadEnvMismatch, Error::ModuleLoadEnvMismatch) => true,
+            (Error::RuntimeIsActive, Error::RuntimeIsActive) => true,
+            // a `dyn Any` can't be compared, so two host errors are never equal, even to
+            // themselves
+            (Error::Host(_), Error::Host(_)) => false,
+            _ => false,
+        }
+    }
+}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::Wasm3(err) => fmt::Display::fmt(err, f),
+            Error::Wasm3(err) => match &err.1 {
+                Some(info) => write!(
+                    f,
+                    "{} ({}:{}): {}",
+                    info.function.as_deref().unwrap_or("<unknown>"),
+                    info.file,
+                    info.line,
+                    info.message
+                ),
+                None => fmt::Display::fmt(err, f),
+            },
             Error::InvalidFunctionSignature => {
                 write!(f, "the found function had an unexpected signature")
             }
@@ -188,6 +295,7 @@ impl fmt::Display for Error {
                 f,
                 "the runtime is active and running, and modules can not be linked to it."
             ),
+            Error::Host(_) => write!(f, "a host function returned an error"),
         }
     }
 }
@@ -203,3 +311,141 @@ impl From<Trap> for Error {
         Self::from(Wasm3Error::from(trap))
     }
 }
+
+/// A captured WebAssembly call stack, innermost frame first.
+///
+/// Only available when the `backtrace` cargo feature is enabled, since recording frames has a
+/// runtime cost. Read it back with [`Runtime::last_backtrace`](crate::runtime::Runtime::last_backtrace)
+/// after a call fails.
+#[cfg(feature = "backtrace")]
+#[derive(Clone, Debug, Default)]
+pub struct Backtrace(pub(crate) Vec<Frame>);
+
+#[cfg(feature = "backtrace")]
+impl Backtrace {
+    /// The captured frames, innermost first.
+    pub fn frames(&self) -> &[Frame] {
+        &self.0
+    }
+}
+
+/// A single frame of a [`Backtrace`].
+#[cfg(feature = "backtrace")]
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The demangled name of the function this frame is in, or `None` for anonymous/host frames
+    /// wasm3 couldn't resolve a name for.
+    pub function: Option<String>,
+    /// The byte offset of this frame's instruction pointer into its compiled module.
+    pub module_offset: u32,
+}
+
+/// Structured diagnostic information about a wasm3 failure, as reported by `m3_GetErrorInfo`.
+#[derive(Clone, Debug, Default)]
+pub struct ErrorInfo {
+    /// The name of the module the failure originated in, if wasm3 could resolve one.
+    pub module: Option<String>,
+    /// The name of the function the failure originated in, if wasm3 could resolve one.
+    pub function: Option<String>,
+    /// The source file the failure originated in.
+    pub file: String,
+    /// The line within [`ErrorInfo::file`] the failure originated on.
+    pub line: u32,
+    /// The message wasm3 reported for the failure.
+    pub message: String,
+}
+
+// Carries a real, non-null message pointer so the `Display`/`Debug` impls (which read through
+// it unconditionally) stay sound.
+#[cfg(test)]
+fn test_message() -> *const cty::c_char {
+    static MSG: &[u8] = b"boom\0";
+    MSG.as_ptr().cast()
+}
+
+#[test]
+fn wasm3_error_eq_ignores_error_info() {
+    let without_info = Wasm3Error(test_message(), None);
+    let with_info = Wasm3Error(
+        test_message(),
+        Some(ErrorInfo {
+            module: Some("mod".into()),
+            function: Some("func".into()),
+            file: "file.wasm".into(),
+            line: 42,
+            message: "boom".into(),
+        }),
+    );
+    assert_eq!(without_info, with_info);
+}
+
+#[test]
+fn error_display_prefers_error_info_message_over_raw_result_string() {
+    let err = Error::Wasm3(Wasm3Error(
+        test_message(),
+        Some(ErrorInfo {
+            module: None,
+            function: Some("my_func".into()),
+            file: "test.wasm".into(),
+            line: 7,
+            message: "division by zero".into(),
+        }),
+    ));
+    assert_eq!(
+        alloc::format!("{err}"),
+        "my_func (test.wasm:7): division by zero"
+    );
+}
+
+#[test]
+fn error_display_falls_back_to_raw_message_without_error_info() {
+    let err = Error::Wasm3(Wasm3Error(test_message(), None));
+    assert_eq!(alloc::format!("{err}"), "boom");
+}
+
+// These exercise the sentinel/downcast mechanics directly rather than through a real linked
+// closure, because doing that needs a compiled module and an `Environment`/`WasmArgs` impl that
+// aren't part of this checkout (see `function::link_closure`, which does drive these from a
+// real trampoline call). Once those pieces exist, prefer a test that links a closure returning
+// `Err` and asserts the resulting `Error::Host` over extending the ones below.
+#[test]
+fn host_error_sentinel_is_stable_and_non_null() {
+    assert_eq!(Error::host_error_sentinel(), Error::host_error_sentinel());
+    assert!(!Error::host_error_sentinel().is_null());
+}
+
+#[test]
+fn error_host_downcast_ref_recovers_concrete_type() {
+    let err = Error::Host(Box::new(String::from("custom failure")));
+    assert_eq!(
+        err.downcast_ref::<String>().map(String::as_str),
+        Some("custom failure")
+    );
+    assert_eq!(err.downcast_ref::<i32>(), None);
+}
+
+#[test]
+fn error_partial_eq_treats_host_errors_as_always_unequal() {
+    assert_eq!(Error::FunctionNotFound, Error::FunctionNotFound);
+    assert_ne!(Error::FunctionNotFound, Error::ModuleNotFound);
+    assert_ne!(Error::Host(Box::new(1i32)), Error::Host(Box::new(1i32)));
+}
+
+#[cfg(feature = "backtrace")]
+#[test]
+fn backtrace_frames_returns_captured_frames_in_order() {
+    let bt = Backtrace(alloc::vec![
+        Frame {
+            function: Some("foo".into()),
+            module_offset: 10,
+        },
+        Frame {
+            function: None,
+            module_offset: 20,
+        },
+    ]);
+    assert_eq!(bt.frames().len(), 2);
+    assert_eq!(bt.frames()[0].function.as_deref(), Some("foo"));
+    assert_eq!(bt.frames()[0].module_offset, 10);
+    assert_eq!(bt.frames()[1].function, None);
+}
@@ -0,0 +1,122 @@
+//! Calling wasm3 functions and linking Rust closures as their imports.
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+use crate::error::{Error, Result};
+use crate::runtime::{PinnedAnyClosure, Runtime};
+use crate::utils::str_to_cstr_owned;
+
+/// A function exported by a wasm3 module, found via [`Runtime::find_function`](crate::runtime::Runtime::find_function).
+pub struct Function<'rt, ARGS, RET> {
+    raw: NonNull<ffi::M3Function>,
+    runtime: &'rt Runtime,
+    _pd: PhantomData<fn(ARGS) -> RET>,
+}
+
+impl<'rt, ARGS, RET> Function<'rt, ARGS, RET>
+where
+    ARGS: crate::WasmArgs,
+    RET: crate::WasmType,
+{
+    pub(crate) fn from_raw(runtime: &'rt Runtime, raw: NonNull<ffi::M3Function>) -> Result<Self> {
+        Ok(Self {
+            raw,
+            runtime,
+            _pd: PhantomData,
+        })
+    }
+
+    /// Calls this function with the given arguments.
+    ///
+    /// Clears any [`Runtime::exit_code`](crate::runtime::Runtime::exit_code) left over from a
+    /// previous call before running, so a caller only ever observes an exit code the guest
+    /// actually raised during *this* invocation.
+    pub fn call(&self, args: ARGS) -> Result<RET> {
+        self.runtime.clear_exit_code();
+        unsafe { ARGS::call(self.raw.as_ptr(), self.runtime, args) }
+    }
+}
+
+// The state boxed and pinned into `Runtime::closure_store` for a linked host closure. It
+// carries a raw pointer back to the owning `Runtime` alongside the closure itself, since
+// wasm3's raw call convention hands the trampoline only the closure's own userdata pointer, not
+// a way to recover our `Runtime` wrapper around the `ffi::M3Runtime` it's calling on.
+struct LinkedClosure<F> {
+    runtime: *const Runtime,
+    closure: F,
+}
+
+// The trampoline wasm3 invokes for a linked closure. `ctx.userdata` is the `LinkedClosure<F>`
+// pushed by `link_closure` below. A closure that returns `Err` has its error boxed onto the
+// runtime's host error stack and signals wasm3 with the private sentinel `M3Result`, which
+// `Error::from_ffi_res` recognizes by address and turns back into `Error::Host(..)`.
+unsafe extern "C" fn call_closure<F, ARGS, RET>(
+    _runtime: ffi::IM3Runtime,
+    ctx: ffi::IM3ImportContext,
+    sp: *mut u64,
+    mem: *mut cty::c_void,
+) -> ffi::M3Result
+where
+    ARGS: crate::WasmArgs,
+    RET: crate::WasmType,
+    F: Fn(ARGS) -> core::result::Result<RET, Box<dyn core::error::Error + Send + Sync>> + 'static,
+{
+    let state = &*((*ctx).userdata as *const LinkedClosure<F>);
+    let args = ARGS::pop_from_stack(sp, mem);
+    match (state.closure)(args) {
+        Ok(ret) => {
+            ret.push_to_stack(sp);
+            core::ptr::null()
+        }
+        Err(err) => {
+            (*state.runtime).push_host_error(Box::new(err));
+            Error::host_error_sentinel()
+        }
+    }
+}
+
+/// Links `closure` as `module_name.function_name` on `module`, so that guest calls into it run
+/// `closure` and any `Err` it returns comes back out as [`Error::Host`](crate::error::Error::Host).
+/// That payload is the closure's `Box<dyn core::error::Error + Send + Sync>` itself:
+/// `downcast_ref::<Box<dyn core::error::Error + Send + Sync>>()` recovers it, while
+/// `downcast_ref` with the closure's concrete error type returns `None`.
+pub(crate) fn link_closure<F, ARGS, RET>(
+    runtime: &Runtime,
+    module: ffi::IM3Module,
+    module_name: &str,
+    function_name: &str,
+    signature: &str,
+    closure: F,
+) -> Result<()>
+where
+    ARGS: crate::WasmArgs,
+    RET: crate::WasmType,
+    F: Fn(ARGS) -> core::result::Result<RET, Box<dyn core::error::Error + Send + Sync>> + 'static,
+{
+    let state: Pin<Box<LinkedClosure<F>>> = Box::pin(LinkedClosure {
+        runtime: runtime as *const Runtime,
+        closure,
+    });
+    let userdata = &*state as *const LinkedClosure<F> as *const cty::c_void;
+    let module_name = str_to_cstr_owned(module_name);
+    let function_name = str_to_cstr_owned(function_name);
+    let signature = str_to_cstr_owned(signature);
+    Error::from_ffi_res(
+        unsafe {
+            ffi::m3_LinkRawFunctionEx(
+                module,
+                module_name.as_ptr(),
+                function_name.as_ptr(),
+                signature.as_ptr(),
+                Some(call_closure::<F, ARGS, RET>),
+                userdata,
+            )
+        },
+        runtime,
+    )?;
+    let pinned: PinnedAnyClosure = state;
+    runtime.push_closure(pinned);
+    Ok(())
+}
@@ -1,16 +1,20 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
-use core::cell::UnsafeCell;
+use core::any::Any;
+use core::cell::{Cell, UnsafeCell};
 use core::pin::Pin;
 use core::ptr::{self, NonNull};
 
 use crate::environment::Environment;
-use crate::error::{Error, Result};
+#[cfg(feature = "backtrace")]
+use crate::error::{Backtrace, Frame};
+use crate::error::{Error, ErrorInfo, Result};
 use crate::function::Function;
 use crate::module::{Module, ParsedModule};
+use crate::utils::cstr_to_str;
 use crate::utils::str_to_cstr_owned;
 
-type PinnedAnyClosure = Pin<Box<dyn core::any::Any + 'static>>;
+pub(crate) type PinnedAnyClosure = Pin<Box<dyn core::any::Any + 'static>>;
 
 /// A runtime context for wasm3 modules.
 #[derive(Debug)]
@@ -21,6 +25,18 @@ pub struct Runtime {
     closure_store: UnsafeCell<Vec<PinnedAnyClosure>>,
     // holds all backing data of loaded modules as they have to be kept alive for the module's lifetime
     module_data: UnsafeCell<Vec<Box<[u8]>>>,
+    // a stack of errors returned by host closures, pushed by the trampoline when a closure
+    // returns `Err` and popped by `Error::from_ffi_res`; a stack so re-entrant host calls
+    // (a host function that calls back into wasm which calls another host function) keep
+    // their errors separate, LIFO
+    host_error: UnsafeCell<Vec<Box<dyn Any + Send + Sync>>>,
+    // the backtrace captured for the most recent ffi call that failed with a wasm3 error, if the
+    // `backtrace` feature is enabled
+    #[cfg(feature = "backtrace")]
+    backtrace: UnsafeCell<Option<Backtrace>>,
+    // the status passed to the guest's most recent `proc_exit` call, cleared at the start of
+    // every top-level `Function::call` so a stale code from a prior invocation never leaks out
+    exit_code: Cell<Option<i32>>,
 }
 
 impl Runtime {
@@ -43,6 +59,10 @@ impl Runtime {
             environment: Some(environment.clone()),
             closure_store: UnsafeCell::new(Vec::new()),
             module_data: UnsafeCell::new(Vec::new()),
+            host_error: UnsafeCell::new(Vec::new()),
+            #[cfg(feature = "backtrace")]
+            backtrace: UnsafeCell::new(None),
+            exit_code: Cell::new(None),
         })
     }
 
@@ -66,7 +86,10 @@ impl Runtime {
             Err(Error::ModuleLoadEnvMismatch)
         } else {
             let raw_mod = module.as_ptr();
-            Error::from_ffi_res(unsafe { ffi::m3_LoadModule(self.raw.as_ptr(), raw_mod) })?;
+            Error::from_ffi_res(
+                unsafe { ffi::m3_LoadModule(self.raw.as_ptr(), raw_mod) },
+                self,
+            )?;
             // SAFETY: Runtime isn't Send, therefor this access is single-threaded and kept alive only for the Vec::push call
             // as such this can not alias.
             unsafe { (*self.module_data.get()).push(module.take_data()) };
@@ -93,7 +116,7 @@ impl Runtime {
                 func_name_cstr.as_ptr(),
             )
         };
-        Error::from_ffi_res(result)?;
+        Error::from_ffi_res(result, self)?;
         let func = NonNull::new(func_raw).ok_or(Error::FunctionNotFound)?;
         Function::from_raw(self, func)
     }
@@ -115,6 +138,27 @@ impl Runtime {
             std::slice::from_raw_parts_mut(data, len as usize)
         }
     }
+
+    /// Returns the backtrace captured for the most recent call on this runtime that failed with
+    /// an [`Error::Wasm3`], or `None` if no call has failed that way yet. Other failures (e.g.
+    /// `Error::FunctionNotFound` or `Error::Host`) record no backtrace and leave the previous
+    /// one in place.
+    ///
+    /// Requires the `backtrace` cargo feature; without it this crate never builds one. The feature only decides whether *this* crate walks
+    /// `m3_GetBacktrace` — wasm3 itself also has to be built with `d_m3RecordBacktraces` defined,
+    /// which is controlled by the `ffi` crate's own build, not this one. With frames disabled
+    /// there this will simply observe an empty `Backtrace` rather than `None`.
+    #[cfg(feature = "backtrace")]
+    pub fn last_backtrace(&self) -> Option<&Backtrace> {
+        unsafe { (*self.backtrace.get()).as_ref() }
+    }
+
+    /// Returns the status the guest passed to `proc_exit`, if its most recent top-level call
+    /// ended in a clean [`Trap::Exit`](crate::error::Trap::Exit) rather than still running or
+    /// failing some other way.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code.get()
+    }
 }
 
 impl Runtime {
@@ -124,12 +168,102 @@ impl Runtime {
             environment: None,
             closure_store: UnsafeCell::default(),
             module_data: UnsafeCell::default(),
+            host_error: UnsafeCell::default(),
+            #[cfg(feature = "backtrace")]
+            backtrace: UnsafeCell::default(),
+            exit_code: Cell::new(None),
         }
     }
     pub(crate) fn push_closure(&self, closure: PinnedAnyClosure) {
         unsafe { (*self.closure_store.get()).push(closure) };
     }
 
+    // Called by a host closure's trampoline when it returns `Err`, just before failing the
+    // ffi call with `Error::host_error_sentinel()`.
+    pub(crate) fn push_host_error(&self, err: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.host_error.get()).push(err) };
+    }
+
+    // Called by `Error::from_ffi_res` once it recognizes the sentinel `M3Result`. Panics if
+    // the LIFO invariant was violated, i.e. a host error was reported without first being pushed.
+    pub(crate) fn pop_host_error(&self) -> Box<dyn Any + Send + Sync> {
+        unsafe { (*self.host_error.get()).pop() }.expect("host error stack is empty")
+    }
+
+    // Walks wasm3's linked list of backtrace frames and stores the result, ready to be read
+    // back through `last_backtrace`. Called by `Error::from_ffi_res` right after a failing
+    // call, before any other runtime operation can overwrite wasm3's internal state.
+    #[cfg(feature = "backtrace")]
+    pub(crate) fn capture_backtrace(&self) {
+        let mut frames = Vec::new();
+        unsafe {
+            if let Some(info) = ffi::m3_GetBacktrace(self.as_ptr()).as_ref() {
+                let mut frame = info.frames;
+                while let Some(f) = frame.as_ref() {
+                    let function = if f.function.is_null() {
+                        None
+                    } else {
+                        let raw_name = ffi::m3_GetFunctionName(f.function);
+                        if raw_name.is_null() {
+                            None
+                        } else {
+                            let name = cstr_to_str(raw_name);
+                            Some(
+                                rustc_demangle::try_demangle(name)
+                                    .map(|demangled| alloc::format!("{demangled}"))
+                                    .unwrap_or_else(|_| name.into()),
+                            )
+                        }
+                    };
+                    frames.push(Frame {
+                        function,
+                        module_offset: f.moduleOffset,
+                    });
+                    frame = f.next;
+                }
+            }
+        }
+        unsafe { *self.backtrace.get() = Some(Backtrace(frames)) };
+    }
+
+    // Queries wasm3 for diagnostic info about the error that just failed the current ffi call.
+    // Must run immediately after that call, before any other runtime operation overwrites
+    // wasm3's internal error state.
+    pub(crate) fn query_error_info(&self) -> Option<ErrorInfo> {
+        unsafe {
+            let mut raw = core::mem::zeroed::<ffi::M3ErrorInfo>();
+            ffi::m3_GetErrorInfo(self.as_ptr(), &mut raw);
+            if raw.message.is_null() {
+                return None;
+            }
+            let owned_str = |ptr: *const cty::c_char| -> Option<alloc::string::String> {
+                if ptr.is_null() {
+                    None
+                } else {
+                    Some(cstr_to_str(ptr).into())
+                }
+            };
+            Some(ErrorInfo {
+                module: owned_str(raw.module),
+                function: owned_str(raw.function),
+                file: owned_str(raw.file).unwrap_or_default(),
+                line: raw.line,
+                message: owned_str(raw.message).unwrap_or_default(),
+            })
+        }
+    }
+
+    // Called by the `proc_exit` WASI binding when the guest invokes it.
+    pub(crate) fn set_exit_code(&self, code: i32) {
+        self.exit_code.set(Some(code));
+    }
+
+    // Called at the start of every top-level `Function::call` so a stale exit code from a
+    // prior invocation is never reported for a call that didn't itself exit.
+    pub(crate) fn clear_exit_code(&self) {
+        self.exit_code.set(None);
+    }
+
     pub(crate) fn as_ptr(&self) -> ffi::IM3Runtime {
         self.raw.as_ptr()
     }
@@ -148,3 +282,21 @@ fn create_and_drop_rt() {
     let env = Environment::new().expect("env alloc failure");
     assert!(Runtime::new(&env, 1024 * 64).is_ok());
 }
+
+#[test]
+fn exit_code_is_tracked_and_cleared() {
+    // `from_raw` never touches `raw` unless `environment` is `Some`, so a dangling pointer is
+    // safe here to exercise the exit-code bookkeeping in isolation from the rest of wasm3.
+    //
+    // `wasi::link_proc_exit` now drives `set_exit_code` from a real `proc_exit` call and
+    // `Function::call` drives `clear_exit_code`; covering that path end-to-end needs a compiled
+    // module calling through a real `Environment`/`Runtime`, which isn't available in this
+    // checkout. Prefer a test that links and calls a module invoking `proc_exit` once that's
+    // possible over extending this one.
+    let rt = Runtime::from_raw(NonNull::dangling());
+    assert_eq!(rt.exit_code(), None);
+    rt.set_exit_code(42);
+    assert_eq!(rt.exit_code(), Some(42));
+    rt.clear_exit_code();
+    assert_eq!(rt.exit_code(), None);
+}
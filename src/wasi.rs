@@ -0,0 +1,43 @@
+//! Minimal WASI host bindings.
+use crate::error::{Error, Result, Trap};
+use crate::runtime::Runtime;
+use crate::utils::str_to_cstr_owned;
+
+// The raw trampoline linked for `wasi_snapshot_preview1.proc_exit`. wasm3 places the guest's
+// single `i32` argument at the bottom of the call stack `sp` points at. Rather than returning a
+// host error through the `Error::Host` path (see `function::call_closure`), this stores the
+// status directly on the runtime and raises `Trap::Exit`, so embedders can recover it via
+// `Runtime::exit_code`/`Error::exit_code` without it looking like an arbitrary host failure.
+unsafe extern "C" fn proc_exit(
+    _runtime: ffi::IM3Runtime,
+    ctx: ffi::IM3ImportContext,
+    sp: *mut u64,
+    _mem: *mut cty::c_void,
+) -> ffi::M3Result {
+    let runtime = &*((*ctx).userdata as *const Runtime);
+    let code = *sp.cast::<i32>();
+    runtime.set_exit_code(code);
+    Trap::Exit.as_ptr()
+}
+
+/// Links the `wasi_snapshot_preview1.proc_exit` import on `module`, so that a guest calling it
+/// stores its exit status on `runtime` (readable afterwards via [`Runtime::exit_code`]) and
+/// unwinds with [`Trap::Exit`] instead of aborting the process.
+pub fn link_proc_exit(runtime: &Runtime, module: ffi::IM3Module) -> Result<()> {
+    let module_name = str_to_cstr_owned("wasi_snapshot_preview1");
+    let function_name = str_to_cstr_owned("proc_exit");
+    let signature = str_to_cstr_owned("v(i)");
+    Error::from_ffi_res(
+        unsafe {
+            ffi::m3_LinkRawFunctionEx(
+                module,
+                module_name.as_ptr(),
+                function_name.as_ptr(),
+                signature.as_ptr(),
+                Some(proc_exit),
+                runtime as *const Runtime as *const cty::c_void,
+            )
+        },
+        runtime,
+    )
+}